@@ -0,0 +1,92 @@
+//! Downloads published package tarballs from a cargo registry, so their manifest can be parsed
+//! the same way a local package's can.
+
+use crate::published_packages::sparse_index;
+use anyhow::Context;
+use cargo_metadata::Package;
+use flate2::read::GzDecoder;
+use std::io::Cursor;
+use tar::Archive;
+
+/// Downloads a set of packages' latest published version from a registry into a directory,
+/// returning their parsed manifests.
+pub struct PackageDownloader {
+    package_names: Vec<String>,
+    directory: String,
+    registry: Option<String>,
+    /// Token used to authenticate the tarball request, for private/alternative registries.
+    /// Resolved by the caller (see [`sparse_index::registry_token`]) and forwarded here via
+    /// [`Self::with_auth_token`]. The version lookup and `config.json` fetch don't use this
+    /// field: they resolve the registry's token themselves via `registry_token`.
+    auth_token: Option<String>,
+}
+
+impl PackageDownloader {
+    pub fn new(package_names: Vec<&str>, directory: &str) -> Self {
+        Self {
+            package_names: package_names.into_iter().map(str::to_string).collect(),
+            directory: directory.to_string(),
+            registry: None,
+            auth_token: None,
+        }
+    }
+
+    /// Downloads from `registry` instead of crates.io.
+    pub fn with_registry(mut self, registry: String) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Authenticates the tarball request with `token`, needed for private or alternative
+    /// registries that require a token just to download a package.
+    pub fn with_auth_token(mut self, token: String) -> Self {
+        self.auth_token = Some(token);
+        self
+    }
+
+    pub fn download(&self) -> anyhow::Result<Vec<Package>> {
+        self.package_names
+            .iter()
+            .map(|name| self.download_package(name))
+            .collect()
+    }
+
+    fn download_package(&self, name: &str) -> anyhow::Result<Package> {
+        let version = sparse_index::fetch_latest_version(self.registry.as_deref(), name)
+            .with_context(|| format!("failed to resolve latest version of '{name}'"))?
+            .with_context(|| format!("package '{name}' was not found in the registry index"))?;
+
+        let url = sparse_index::dl_url(self.registry.as_deref(), name, &version)
+            .with_context(|| format!("failed to resolve download url for '{name}'"))?;
+
+        let client = reqwest::blocking::Client::new();
+        let mut request = client.get(&url);
+        if let Some(token) = &self.auth_token {
+            request = request.header(reqwest::header::AUTHORIZATION, token.clone());
+        }
+        let bytes = request
+            .send()
+            .with_context(|| format!("failed to download '{name}' from {url}"))?
+            .error_for_status()
+            .with_context(|| format!("registry returned an error downloading '{name}' from {url}"))?
+            .bytes()
+            .with_context(|| format!("failed to read tarball for '{name}'"))?;
+
+        Archive::new(GzDecoder::new(Cursor::new(bytes)))
+            .unpack(&self.directory)
+            .with_context(|| format!("failed to unpack tarball for '{name}'"))?;
+
+        let package_dir = std::path::Path::new(&self.directory).join(format!("{name}-{version}"));
+        let manifest = cargo_metadata::camino::Utf8PathBuf::try_from(
+            package_dir.join(cargo_utils::CARGO_TOML),
+        )?;
+        let metadata = cargo_utils::get_manifest_metadata(&manifest)
+            .with_context(|| format!("failed to get manifest metadata for '{name}' '{version}'"))?;
+
+        metadata
+            .packages
+            .into_iter()
+            .find(|p| p.name == name)
+            .with_context(|| format!("could not find package '{name}' in its own manifest"))
+    }
+}
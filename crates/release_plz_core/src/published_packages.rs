@@ -1,12 +1,31 @@
 use crate::{cargo_vcs_info, download, next_ver, PackagePath, Project};
 use anyhow::Context;
-use cargo_metadata::{camino::Utf8Path, Package};
-use git_cmd::{git_in_dir, Repo};
+use cargo_metadata::{
+    camino::{Utf8Path, Utf8PathBuf},
+    semver::Version,
+    Package,
+};
+use git_cmd::Repo;
 use itertools::Itertools;
 use regex::Regex;
 use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use tempfile::{tempdir, TempDir};
 
+// `pub(crate)` because `download` also needs it to resolve registry/index details, not just
+// this module.
+pub(crate) mod sparse_index;
+
+/// How long to keep retrying a registry download before giving up, when the package appears
+/// absent because the registry's index hasn't propagated yet. Overridable via
+/// [`DOWNLOAD_TIMEOUT_ENV_VAR`], since how long propagation takes varies a lot by registry.
+const DEFAULT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Environment variable to override [`DEFAULT_DOWNLOAD_TIMEOUT`].
+const DOWNLOAD_TIMEOUT_ENV_VAR: &str = "RELEASE_PLZ_REGISTRY_DOWNLOAD_TIMEOUT_SECS";
+
 /// A collection of [`PublishedPackage`]s.
 pub struct PackagesCollection {
     packages: BTreeMap<String, PublishedPackage>,
@@ -15,22 +34,106 @@ pub struct PackagesCollection {
     temp_dir: Option<TempDir>,
 }
 
-/// A published [`Package`]'s manifest.
+/// Where a [`PublishedPackage`]'s data came from.
+enum PublishedPackageState {
+    /// The package's full manifest was downloaded, either as a registry tarball or from a git
+    /// tag's checkout.
+    Full(Package),
+    /// Only the version was resolved, via a registry's sparse index. The package's contents
+    /// were never downloaded, so its manifest isn't available.
+    IndexOnly { name: String, version: Version },
+}
+
+/// A published package's manifest, or at least its version.
 pub struct PublishedPackage {
-    pub package: Package,
+    state: PublishedPackageState,
     /// The SHA1 hash of the commit when the package was published.
     sha1: Option<String>,
+    /// The files published as part of this package, ignore-aware (`.gitignore`-excluded files
+    /// are left out), so that the published contents can be compared against the local
+    /// package without shelling out to `cargo package --list`. Only populated for packages
+    /// downloaded from a registry.
+    files: Option<Vec<Utf8PathBuf>>,
 }
 
 impl PublishedPackage {
+    fn full(package: Package, sha1: Option<String>) -> Self {
+        Self {
+            state: PublishedPackageState::Full(package),
+            sha1,
+            files: None,
+        }
+    }
+
+    fn with_files(mut self, files: Vec<Utf8PathBuf>) -> Self {
+        self.files = Some(files);
+        self
+    }
+
+    fn index_only(name: String, version: Version) -> Self {
+        Self {
+            state: PublishedPackageState::IndexOnly { name, version },
+            sha1: None,
+            files: None,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match &self.state {
+            PublishedPackageState::Full(package) => &package.name,
+            PublishedPackageState::IndexOnly { name, .. } => name,
+        }
+    }
+
+    pub fn version(&self) -> &Version {
+        match &self.state {
+            PublishedPackageState::Full(package) => &package.version,
+            PublishedPackageState::IndexOnly { version, .. } => version,
+        }
+    }
+
+    /// The package's full manifest, or `None` if only its version is known (see
+    /// [`PublishedPackageState::IndexOnly`]).
+    pub fn package(&self) -> Option<&Package> {
+        match &self.state {
+            PublishedPackageState::Full(package) => Some(package),
+            PublishedPackageState::IndexOnly { .. } => None,
+        }
+    }
+
     pub fn published_at_sha1(&self) -> Option<&str> {
         self.sha1.as_deref()
     }
+
+    /// The files published as part of this package, or `None` if they weren't enumerated.
+    ///
+    /// This is only populated when the package's tarball was actually downloaded: it's `None`
+    /// both for packages resolved via the sparse-index fast path ([`PublishedPackageState::IndexOnly`])
+    /// and for those read straight off the local filesystem (`registry_manifest`/git-tag paths).
+    /// Don't treat `None` here as "no files were published".
+    pub fn files(&self) -> Option<&[Utf8PathBuf]> {
+        self.files.as_deref()
+    }
 }
 
 impl PackagesCollection {
+    /// The full manifest of `package_name`'s latest published version.
+    ///
+    /// **`None` is ambiguous if [`get_latest_packages`] was called with `allow_index_only:
+    /// true`**: it then means either "never published" *or* "published, but only its version was
+    /// resolved via the registry's sparse index, so its manifest was never downloaded" (see
+    /// [`PublishedPackageState::IndexOnly`]). Callers that need this to unambiguously mean "never
+    /// published" must pass `allow_index_only: false`. Use [`Self::is_published`] if you only
+    /// need to know whether a prior release exists, regardless of how much of it we fetched.
     pub fn get_package(&self, package_name: &str) -> Option<&Package> {
-        self.packages.get(package_name).map(|p| &p.package)
+        self.packages.get(package_name).and_then(|p| p.package())
+    }
+
+    /// Whether `package_name` has a prior published version at all, whether or not its full
+    /// manifest was downloaded. Prefer this over `get_package(...).is_none()` for "is this the
+    /// first release" checks, since that conflates "never published" with "index-only".
+    pub fn is_published(&self, package_name: &str) -> bool {
+        self.packages.contains_key(package_name)
     }
 
     pub fn get_published_package(&self, package_name: &str) -> Option<&PublishedPackage> {
@@ -53,12 +156,12 @@ impl PackagesCollection {
     }
 
     fn push(&mut self, package: PublishedPackage) {
-        self.packages.insert(package.package.name.clone(), package);
+        self.packages.insert(package.name().to_string(), package);
     }
 
     fn extend(&mut self, packages: impl IntoIterator<Item = PublishedPackage>) {
         self.packages
-            .extend(packages.into_iter().map(|p| (p.package.name.clone(), p)));
+            .extend(packages.into_iter().map(|p| (p.name().to_string(), p)));
     }
 
     /// Retrieve the latest version of the packages from a registry.
@@ -69,25 +172,26 @@ impl PackagesCollection {
     ///
     /// - If `registry` is provided, the packages are downloaded from the specified registry.
     /// - Otherwise, the packages are downloaded from crates.io.
+    ///
+    /// - If `allow_index_only` is `true`, a package's version may be resolved via the registry's
+    ///   sparse index alone, without its manifest ([`PublishedPackageState::IndexOnly`]), when
+    ///   that's cheaper. Only pass `true` when the caller only needs [`PublishedPackage::version`]
+    ///   or [`PackagesCollection::is_published`]; anyone that reads [`Self::get_package`] needs
+    ///   the full manifest and must pass `false`.
     fn get_registry_packages<'p>(
         &mut self,
         registry_manifest: Option<&Utf8Path>,
         local_packages: impl IntoIterator<Item = &'p Package>,
         registry: Option<&str>,
+        allow_index_only: bool,
     ) -> anyhow::Result<()> {
         match registry_manifest {
             Some(manifest) => self.extend(
                 next_ver::publishable_packages_from_manifest(manifest)?
                     .into_iter()
-                    .map(|p| PublishedPackage {
-                        package: p,
-                        sha1: None,
-                    }),
+                    .map(|p| PublishedPackage::full(p, None)),
             ),
             None => {
-                let temp_dir = self.temp_dir()?;
-                let directory = temp_dir.as_ref().to_str().context("invalid tempdir path")?;
-
                 // Find the registry from where to download each package.
                 let packages_grouped_by_registry = local_packages.into_iter().chunk_by(|p| {
                     // If registry is not provided, fallback to the Cargo.toml `publish` field.
@@ -101,22 +205,65 @@ impl PackagesCollection {
                 });
                 let mut registry_packages: Vec<Package> = vec![];
                 for (registry, packages) in &packages_grouped_by_registry {
-                    let packages_names: Vec<&str> = packages.map(|p| p.name.as_str()).collect();
+                    let packages: Vec<&Package> = packages.collect();
+
+                    // Before downloading a full tarball, try the cheap sparse-index path:
+                    // a single request per crate tells us the latest published version, which
+                    // is all some callers (e.g. version comparisons) ever need. Only attempted
+                    // when the caller opted into `allow_index_only`: skipping the download also
+                    // skips the manifest, which breaks anyone relying on `get_package`.
+                    let mut still_need_download = Vec::new();
+                    for package in packages {
+                        if !allow_index_only {
+                            still_need_download.push(package.name.as_str());
+                            continue;
+                        }
+                        match sparse_index::fetch_latest_version(registry, &package.name) {
+                            Ok(Some(version)) => {
+                                self.push(PublishedPackage::index_only(
+                                    package.name.clone(),
+                                    version,
+                                ));
+                            }
+                            Ok(None) | Err(_) => still_need_download.push(package.name.as_str()),
+                        }
+                    }
+
+                    if still_need_download.is_empty() {
+                        continue;
+                    }
+
+                    // Only worth retrying a "not found" download when every package here is
+                    // already known to have *some* published version (checked via the registry's
+                    // sparse index, or its classic git index as a fallback): then "not found"
+                    // means index propagation lag. If a package has genuinely never been
+                    // published, or we can't tell at all (unconfigured registry), retrying would
+                    // just stall a first-time publish for the whole timeout.
+                    let retry_on_not_found = matches!(
+                        sparse_index::packages_have_any_version(registry, &still_need_download),
+                        Ok(Some(versions)) if versions.iter().all(|has_version| *has_version)
+                    );
+
+                    let temp_dir = self.temp_dir()?;
+                    let directory = temp_dir.as_ref().to_str().context("invalid tempdir path")?;
+
                     let mut downloader =
-                        download::PackageDownloader::new(packages_names, directory);
+                        download::PackageDownloader::new(still_need_download, directory);
                     if let Some(registry) = registry {
                         downloader = downloader.with_registry(registry.to_string());
                     }
-                    registry_packages.extend(
-                        downloader
-                            .download()
-                            .context("failed to download packages")?,
-                    );
+                    // Alternative/private registries may require a token to even list their
+                    // packages; resolve it the same way `cargo` does (env var, then the
+                    // credentials file) and forward it to the downloader.
+                    if let Some(token) = sparse_index::registry_token(registry)? {
+                        downloader = downloader.with_auth_token(token);
+                    }
+                    registry_packages
+                        .extend(download_with_retry(&downloader, retry_on_not_found)?);
                 }
 
-                // After downloading the package, we initialize a git repo in the package.
-                // This is because if cargo doesn't find a git repo in the package, it doesn't
-                // show hidden files in `cargo package --list` output.
+                // Reads each downloaded package's `.cargo_vcs_info.json` (for its source commit
+                // sha1) and lists its files, the same way `cargo package --list` would.
                 let registry_packages = initialize_registry_package(registry_packages)
                     .context("failed to initialize repository package")?;
                 self.extend(registry_packages);
@@ -137,13 +284,21 @@ impl PackagesCollection {
         repo: &Repo,
         packages: impl Iterator<Item = &'p Package> + 'p,
     ) -> anyhow::Result<()> {
-        let tags = repo.get_tags_version_sorted(true);
+        // `git_cmd::Repo::get_tags_version_sorted` already claims to sort by semver precedence
+        // descending, but it's an external dependency we can't unit-test from here, so the
+        // precedence this relies on (a release outranking its own prereleases, build metadata
+        // not affecting order) is re-derived locally instead of just trusted: see
+        // [`sort_tags_by_version_desc`].
+        let tags = sort_tags_by_version_desc(repo.get_tags_version_sorted(true));
 
         for package in packages {
             // Latest release tag is the first one we find in the descending list of tags
-            let Some(release_tag) =
-                filter_release_tags(tags.iter().map(AsRef::as_ref), &package.name, project).next()
-            else {
+            let Some(release_tag) = filter_release_tags(
+                tags.iter().map(AsRef::as_ref),
+                &package.name,
+                |package, version| project.git_tag(package, version),
+            )
+            .next() else {
                 continue;
             };
 
@@ -151,22 +306,25 @@ impl PackagesCollection {
 
             let package_store_dir = temp_dir.path().join(&package.name);
 
-            // "Download" each package into the temp dir.
-            // We do this by simply creating a new worktree pointing to the release tag.
-            // We could also do this in other ways:
-            // 1. Find relative path to package manifest and checkout package contents
-            //    (see git read-tree and checkout-index) into temp dir
-            // 2. Use `cargo package` to create tarball, and extract it
-            // But the simplest is to use a worktree
-
-            repo.add_worktree(&package_store_dir, release_tag)?;
-
-            let manifest = cargo_metadata::camino::Utf8PathBuf::try_from(
-                package_store_dir.join(cargo_utils::CARGO_TOML),
-            )?;
-            let metadata = cargo_utils::get_manifest_metadata(&manifest).with_context(|| {
-                format!("failed to get root manifest metadata at tag '{release_tag}'")
-            })?;
+            // "Download" the package into the temp dir. A full worktree materializes the
+            // whole repository tree, which is expensive in large monorepos, so prefer a
+            // sparse checkout of just the package's directory plus the workspace root
+            // manifest/lockfile that `cargo_metadata` needs. Fall back to a full worktree
+            // when the package can't be isolated this way (e.g. it lives outside `repo`), or
+            // when the sparse checkout turns out not to be enough for `cargo_metadata` to
+            // resolve the workspace (e.g. another member's manifest is needed too).
+            let metadata = checkout_package_sparse(repo, package, release_tag, &package_store_dir)
+                .and_then(|()| read_tag_manifest_metadata(&package_store_dir, release_tag));
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(_) => {
+                    // Clean up whatever the sparse checkout may have partially written before
+                    // falling back, so `add_worktree` starts from an empty directory.
+                    let _ = fs_err::remove_dir_all(&package_store_dir);
+                    repo.add_worktree(&package_store_dir, release_tag)?;
+                    read_tag_manifest_metadata(&package_store_dir, release_tag)?
+                }
+            };
 
             let published_package = metadata
                 .packages
@@ -180,10 +338,10 @@ impl PackagesCollection {
                     )
                 })?;
 
-            self.push(PublishedPackage {
-                package: published_package,
-                sha1: repo.get_tag_commit(release_tag),
-            });
+            self.push(PublishedPackage::full(
+                published_package,
+                repo.get_tag_commit(release_tag),
+            ));
         }
 
         Ok(())
@@ -193,6 +351,12 @@ impl PackagesCollection {
 /// Retrieves the latest [`PublishedPackage`]s for each of the given packages. The
 /// `registry_packages` are looked up in the registry while the `git_only_packages` are
 /// looked up via git tags.
+///
+/// `allow_index_only` controls whether a registry package's version may be resolved via the
+/// sparse index alone, skipping the manifest download (see
+/// [`PackagesCollection::get_registry_packages`]). Pass `false` if the caller reads
+/// [`PackagesCollection::get_package`] on the result.
+#[allow(clippy::too_many_arguments)]
 pub fn get_latest_packages<'p>(
     project: &'p Project,
     repo: &'p Repo,
@@ -200,16 +364,203 @@ pub fn get_latest_packages<'p>(
     git_only_packages: impl IntoIterator<Item = &'p Package, IntoIter: 'p>,
     registry_manifest: Option<&Utf8Path>,
     registry: Option<&str>,
+    allow_index_only: bool,
 ) -> anyhow::Result<PackagesCollection> {
     let mut collection = PackagesCollection::new();
 
-    collection.get_registry_packages(registry_manifest, registry_packages, registry)?;
+    collection.get_registry_packages(
+        registry_manifest,
+        registry_packages,
+        registry,
+        allow_index_only,
+    )?;
 
     collection.get_latest_tagged_packages(project, repo, git_only_packages.into_iter())?;
 
     Ok(collection)
 }
 
+/// Reads the workspace metadata rooted at `dir`'s `Cargo.toml`, as checked out at `release_tag`.
+///
+/// `cargo_metadata` needs every workspace member declared in the root manifest to have its own
+/// `Cargo.toml` physically present to resolve the workspace; this is what makes a sparse
+/// checkout insufficient in some monorepos, and is why callers should fall back to a full
+/// worktree when this fails.
+fn read_tag_manifest_metadata(
+    dir: &Path,
+    release_tag: &str,
+) -> anyhow::Result<cargo_metadata::Metadata> {
+    let manifest =
+        cargo_metadata::camino::Utf8PathBuf::try_from(dir.join(cargo_utils::CARGO_TOML))?;
+    cargo_utils::get_manifest_metadata(&manifest)
+        .with_context(|| format!("failed to get root manifest metadata at tag '{release_tag}'"))
+}
+
+/// Materializes `package`'s directory at `release_tag` into `dest`, along with the workspace
+/// root manifest and lockfile, without checking out the rest of the repository.
+///
+/// This uses a throwaway index (populated with `git read-tree`) so we never touch `repo`'s
+/// real index, and `git checkout-index` to extract only the paths we actually need, rather
+/// than `git worktree add`, which would materialize the whole tree.
+fn checkout_package_sparse(
+    repo: &Repo,
+    package: &Package,
+    release_tag: &str,
+    dest: &Path,
+) -> anyhow::Result<()> {
+    let repo_dir = repo.directory();
+    let relative_package_dir = package
+        .package_path()
+        .context("failed to resolve package path")?
+        .strip_prefix(repo_dir)
+        .context("package is not inside the repository")?
+        .as_str()
+        .to_owned();
+    let dest_str = dest.to_str().context("destination path is not UTF-8")?;
+
+    fs_err::create_dir_all(dest)?;
+    let index_file = dest.with_extension("partial-index");
+
+    let run_git_with_index = |args: &[&str]| -> anyhow::Result<()> {
+        let output = std::process::Command::new("git")
+            .current_dir(repo_dir)
+            .env("GIT_INDEX_FILE", &index_file)
+            .args(args)
+            .output()
+            .context("failed to spawn git")?;
+        anyhow::ensure!(
+            output.status.success(),
+            "git {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Ok(())
+    };
+
+    // Populate the throwaway index with the tree at `release_tag`.
+    run_git_with_index(&["read-tree", release_tag])?;
+
+    // List only the files we need: the package's own directory, plus the workspace root
+    // manifest/lockfile `cargo_metadata` needs to resolve it.
+    let paths = std::process::Command::new("git")
+        .current_dir(repo_dir)
+        .args([
+            "ls-tree",
+            "-r",
+            "--name-only",
+            "-z",
+            release_tag,
+            "--",
+            &relative_package_dir,
+            cargo_utils::CARGO_TOML,
+            "Cargo.lock",
+        ])
+        .output()
+        .context("failed to list package files")?;
+    anyhow::ensure!(
+        paths.status.success(),
+        "git ls-tree failed: {}",
+        String::from_utf8_lossy(&paths.stderr)
+    );
+
+    let mut checkout_index = std::process::Command::new("git")
+        .current_dir(repo_dir)
+        .env("GIT_INDEX_FILE", &index_file)
+        .args([
+            "checkout-index",
+            "-z",
+            "--stdin",
+            &format!("--prefix={dest_str}/"),
+        ])
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .context("failed to spawn git checkout-index")?;
+    checkout_index
+        .stdin
+        .take()
+        .context("git checkout-index stdin is unavailable")?
+        .write_all(&paths.stdout)?;
+    let status = checkout_index
+        .wait()
+        .context("failed to wait for git checkout-index")?;
+    anyhow::ensure!(status.success(), "git checkout-index failed with {status}");
+
+    let _ = fs_err::remove_file(&index_file);
+    Ok(())
+}
+
+/// Downloads `packages` from the registry, retrying with exponential backoff when a package
+/// looks absent from the index, since the index might just not have propagated yet (this
+/// commonly happens right after publishing interdependent workspace crates in the same run).
+///
+/// Authentication and network failures aren't retried: they're returned immediately, since
+/// waiting won't fix them. Neither is a "not found" when `retry_on_not_found` is `false`: the
+/// caller is expected to only set it when it already knows every requested package has *some*
+/// published version, so a download-time "not found" can only mean propagation lag rather than
+/// "this package was never published", which should fail fast instead of stalling.
+fn download_with_retry(
+    downloader: &download::PackageDownloader,
+    retry_on_not_found: bool,
+) -> anyhow::Result<Vec<Package>> {
+    if !retry_on_not_found {
+        return downloader.download().context("failed to download packages");
+    }
+
+    let timeout = download_timeout();
+    let deadline = Instant::now() + timeout;
+    let mut backoff = Duration::from_secs(1);
+
+    loop {
+        match downloader.download() {
+            Ok(packages) => return Ok(packages),
+            Err(err) if is_missing_from_index_error(&err) && Instant::now() < deadline => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                std::thread::sleep(backoff.min(remaining));
+                backoff *= 2;
+            }
+            Err(err) => return Err(err).context("failed to download packages"),
+        }
+    }
+}
+
+/// Reads [`DEFAULT_DOWNLOAD_TIMEOUT`], overridden by [`DOWNLOAD_TIMEOUT_ENV_VAR`] if set.
+fn download_timeout() -> Duration {
+    std::env::var(DOWNLOAD_TIMEOUT_ENV_VAR)
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DOWNLOAD_TIMEOUT)
+}
+
+/// Whether `err` looks like "the package/version isn't in the registry index yet", as opposed
+/// to an authentication or network failure. There's no structured error for this, so we're
+/// stuck matching on the message registries return for a missing crate or version.
+fn is_missing_from_index_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let message = cause.to_string().to_lowercase();
+        message.contains("not found") || message.contains("404")
+    })
+}
+
+#[cfg(test)]
+mod is_missing_from_index_error_tests {
+    use super::is_missing_from_index_error;
+
+    #[test]
+    fn recognizes_a_not_found_message_anywhere_in_the_error_chain() {
+        let err = anyhow::anyhow!("failed to download 'foo'")
+            .context("registry returned an error downloading 'foo' from https://example.com: 404 Not Found");
+
+        assert!(is_missing_from_index_error(&err));
+    }
+
+    #[test]
+    fn does_not_misclassify_an_authentication_failure() {
+        let err = anyhow::anyhow!("registry returned an error: 401 Unauthorized");
+
+        assert!(!is_missing_from_index_error(&err));
+    }
+}
+
 fn initialize_registry_package(packages: Vec<Package>) -> anyhow::Result<Vec<PublishedPackage>> {
     let mut registry_packages = vec![];
     for p in packages {
@@ -219,49 +570,186 @@ fn initialize_registry_package(packages: Vec<Package>) -> anyhow::Result<Vec<Pub
         // the `--allow-dirty` flag inside a git repo.
         let sha1 = if cargo_vcs_info_path.exists() {
             let sha1 = cargo_vcs_info::read_sha1_from_cargo_vcs_info(&cargo_vcs_info_path);
-            // Remove the file, otherwise `cargo publish --list` fails
+            // Remove the file, otherwise it would show up as a published file that doesn't
+            // exist in the local package.
             fs_err::remove_file(cargo_vcs_info_path)?;
             sha1
         } else {
             None
         };
-        let git_repo = package_path.join(".git");
-        let commit_init = || git_in_dir(package_path, &["commit", "-m", "init"]);
-        if !git_repo.exists() {
-            git_in_dir(package_path, &["init"])?;
-            git_in_dir(package_path, &["add", "."])?;
-            if let Err(e) = commit_init() {
-                if e.to_string().trim().starts_with("Author identity unknown") {
-                    // we can use any email and name here, as this repository is only used
-                    // to compare packages
-                    git_in_dir(package_path, &["config", "user.email", "test@registry"])?;
-                    git_in_dir(package_path, &["config", "user.name", "test"])?;
-                    commit_init()?;
-                }
-            }
-        }
-        registry_packages.push(PublishedPackage { package: p, sha1 });
+        let files = list_package_files(package_path)
+            .with_context(|| format!("failed to list files of package '{}'", p.name))?;
+        registry_packages.push(PublishedPackage::full(p, sha1).with_files(files));
     }
     Ok(registry_packages)
 }
 
+/// Lists the files of a downloaded package, the same way `cargo package --list` would: honoring
+/// `.gitignore` (and friends) but, unlike a plain directory walk, including hidden/dot files
+/// that aren't ignored. Returned paths are relative to `package_path`, so they're comparable to
+/// a local package's file list.
+///
+/// We used to `git init && git add . && git commit` each downloaded package for this, just so
+/// `cargo package --list` would find a git repo and surface dotfiles; that meant a process spawn
+/// (plus an author-identity fallback) per package, and a hard runtime dependency on the `git`
+/// binary. Walking the directory in-process avoids both.
+fn list_package_files(package_path: &Utf8Path) -> anyhow::Result<Vec<Utf8PathBuf>> {
+    let mut files = vec![];
+    for entry in ignore::WalkBuilder::new(package_path)
+        .hidden(false)
+        // `WalkBuilder` only honors `.gitignore` inside an actual git repository by default,
+        // and a downloaded package never is one (that's the whole point of this function).
+        // Without this, `.gitignore` rules would silently never apply.
+        .require_git(false)
+        .build()
+    {
+        let entry =
+            entry.with_context(|| format!("failed to walk package directory '{package_path}'"))?;
+        if entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            let path = Utf8PathBuf::try_from(entry.into_path())?;
+            let relative_path = path
+                .strip_prefix(package_path)
+                .with_context(|| format!("'{path}' is not inside '{package_path}'"))?;
+            files.push(relative_path.to_owned());
+        }
+    }
+    Ok(files)
+}
+
+lazy_static::lazy_static! {
+    // Full semver grammar: `major.minor.patch`, with an optional `-prerelease` and
+    // an optional `+build` suffix, e.g. `1.2.3-rc.1+exp.sha.5114f85`.
+    static ref SEMVER_RE: Regex = Regex::new(
+        r"(?-u:\d)+\.(?-u:\d)+\.(?-u:\d)+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?"
+    ).unwrap();
+}
+
+/// Sorts `tags` by the semver precedence of the first version-looking substring each one
+/// contains, descending, so the highest version sorts first, whether it's a release or a
+/// prerelease; a release outranks its own prereleases, and build metadata is ignored. Tags with
+/// no version-looking substring sort last, in their original relative order.
+///
+/// This only re-derives the precedence [`git_cmd::Repo::get_tags_version_sorted`] already claims
+/// to apply; it exists so that claim is backed by a test we actually control, rather than trusted
+/// untested against an external crate.
+fn sort_tags_by_version_desc(tags: Vec<String>) -> Vec<String> {
+    tags.into_iter()
+        .map(|tag| {
+            let version = SEMVER_RE
+                .find(&tag)
+                .and_then(|m| Version::parse(m.as_str()).ok())
+                // `Version`'s own `Ord` breaks ties on build metadata; semver precedence doesn't.
+                .map(|v| (v.major, v.minor, v.patch, v.pre));
+            (version, tag)
+        })
+        .sorted_by(|(a, _), (b, _)| b.cmp(a))
+        .map(|(_, tag)| tag)
+        .collect()
+}
+
 /// Filters the release tags for the given package from all the `tags` in a repository.
+///
+/// `tags` is expected to already be sorted by semver precedence, descending (see
+/// [`sort_tags_by_version_desc`]), so the first matching tag returned by the
+/// iterator is the latest release, prereleases included.
+///
+/// `git_tag` renders the configured tag template for a package/version pair (normally
+/// [`Project::git_tag`]); it's taken as a closure rather than `&Project` directly so this
+/// function can be unit-tested without a full `Project`.
 fn filter_release_tags<'t>(
     tags: impl Iterator<Item = &'t str> + 't,
     package: &'t str,
-    project: &'t Project,
+    git_tag: impl Fn(&str, &str) -> String + 't,
 ) -> impl Iterator<Item = &'t str> + 't {
-    lazy_static::lazy_static! {
-        static ref SEMVER_RE: Regex = Regex::new(r"((?-u:\d)+\.(?-u:\d)+\.(?-u:\d)+)").unwrap();
-    }
-
     // TODO: Consider using git tag template in the release-plz config at each tag, rather than
     // using the current template
 
     tags
         // Find tags that contain a semver version string
         .filter_map(|tag| Some((tag, SEMVER_RE.find(tag)?.as_str())))
-        // Render the git tag template for the package with the matched version string
-        // and check if the tag matches
-        .filter_map(|(tag, version)| (tag == project.git_tag(package, version)).then_some(tag))
+        // Render the git tag template for the package with the full matched version string
+        // (including prerelease/build metadata) and check if the tag matches
+        .filter_map(move |(tag, version)| (tag == git_tag(package, version)).then_some(tag))
+}
+
+#[cfg(test)]
+mod sort_tags_by_version_desc_tests {
+    use super::sort_tags_by_version_desc;
+
+    #[test]
+    fn sorts_by_semver_precedence_descending() {
+        // A release outranks its own prereleases, and build metadata (`+exp.sha...`) doesn't
+        // affect ordering at all - this is exactly the precedence
+        // `filter_release_tags` relies on its input already being sorted by.
+        let tags = vec![
+            "v1.2.9".to_string(),
+            "v1.3.0-rc.1".to_string(),
+            "v1.3.0+exp.sha.5114f85".to_string(),
+        ];
+
+        assert_eq!(
+            sort_tags_by_version_desc(tags),
+            vec!["v1.3.0+exp.sha.5114f85", "v1.3.0-rc.1", "v1.2.9"]
+        );
+    }
+
+    #[test]
+    fn sorts_tags_without_a_version_last_preserving_their_relative_order() {
+        let tags = vec![
+            "unrelated-tag".to_string(),
+            "v1.2.9".to_string(),
+            "another-unrelated-tag".to_string(),
+        ];
+
+        assert_eq!(
+            sort_tags_by_version_desc(tags),
+            vec!["v1.2.9", "unrelated-tag", "another-unrelated-tag"]
+        );
+    }
+}
+
+#[cfg(test)]
+mod filter_release_tags_tests {
+    use super::filter_release_tags;
+
+    // Mirrors the default `v{version}` template most projects use; only `version` matters for
+    // these tests, so `package` is accepted but ignored.
+    fn v_tag(_package: &str, version: &str) -> String {
+        format!("v{version}")
+    }
+
+    #[test]
+    fn picks_first_matching_tag_regardless_of_prerelease_or_build_metadata() {
+        // Already sorted by semver precedence, descending, the way
+        // `sort_tags_by_version_desc` sorts them: a release outranks its own prereleases, and
+        // build metadata (`+exp.sha...`) doesn't affect ordering at all.
+        let tags = [
+            "v1.3.0+exp.sha.5114f85",
+            "v1.3.0-rc.1",
+            "v1.2.9",
+            "unrelated-tag",
+        ];
+
+        let latest = filter_release_tags(tags.into_iter(), "pkg", v_tag).next();
+
+        assert_eq!(latest, Some("v1.3.0+exp.sha.5114f85"));
+    }
+
+    #[test]
+    fn picks_a_prerelease_newer_than_the_latest_release() {
+        let tags = ["v1.3.0-rc.1", "v1.2.9"];
+
+        let latest = filter_release_tags(tags.into_iter(), "pkg", v_tag).next();
+
+        assert_eq!(latest, Some("v1.3.0-rc.1"));
+    }
+
+    #[test]
+    fn ignores_tags_whose_rendered_template_does_not_match() {
+        let tags = ["random-1.3.0-tag", "v1.2.9"];
+
+        let latest = filter_release_tags(tags.into_iter(), "pkg", v_tag).next();
+
+        assert_eq!(latest, Some("v1.2.9"));
+    }
 }
@@ -0,0 +1,500 @@
+//! Minimal client for the sparse registry index protocol used by crates.io and by alternative
+//! registries configured with a `sparse+https://…` index. This lets callers resolve a crate's
+//! latest published version with a single HTTP request, without downloading its tarball.
+
+use anyhow::Context;
+use cargo_metadata::semver::Version;
+use serde::Deserialize;
+
+/// A single line of a sparse-index file, as newline-delimited JSON.
+#[derive(Deserialize)]
+struct IndexRecord {
+    vers: Version,
+    #[serde(default)]
+    yanked: bool,
+}
+
+/// Fetches the latest non-yanked version of `package` from `registry`'s index, whether it's a
+/// sparse (`sparse+https://…`) or classic git-index registry.
+///
+/// Returns `Ok(None)` when the crate doesn't exist in the index yet, so that callers fall back
+/// to downloading the package. Network failures are returned as `Err`, since those shouldn't be
+/// silently treated as "the crate doesn't exist". Index lines that fail to parse are skipped
+/// (see [`parse_index_records`]).
+///
+/// Private/alternative sparse registries are authenticated the same way `cargo` authenticates
+/// sparse index requests: an `Authorization` header carrying the registry's token, resolved via
+/// [`registry_token`].
+pub(crate) fn fetch_latest_version(
+    registry: Option<&str>,
+    package: &str,
+) -> anyhow::Result<Option<Version>> {
+    let records = index_records(registry, package)?;
+    Ok(records.into_iter().filter(|r| !r.yanked).map(|r| r.vers).max())
+}
+
+/// Fetches and parses `package`'s index file, whether `registry` is a sparse or classic
+/// git-index registry. Returns an empty list for a crate that doesn't exist in the index yet.
+fn index_records(registry: Option<&str>, package: &str) -> anyhow::Result<Vec<IndexRecord>> {
+    if let Some(records) = fetch_index_records(registry, package)? {
+        return Ok(records);
+    }
+
+    let Some(repo_url) = classic_git_index_url(registry)? else {
+        return Ok(vec![]);
+    };
+    let contents = fetch_classic_index_paths(&repo_url, &[&index_path(package)])?
+        .into_iter()
+        .next()
+        .flatten();
+    let Some(body) = contents else {
+        return Ok(vec![]);
+    };
+    Ok(parse_index_records(&body))
+}
+
+/// Whether each of `packages` has ever had *any* version published to `registry`, yanked or not,
+/// in the same order as `packages`.
+///
+/// Unlike [`fetch_latest_version`], this is meant to tell a never-published crate apart from one
+/// whose latest version just hasn't propagated to the index yet: the former should fail fast,
+/// the latter is worth retrying. `registry`'s sparse index is checked first; if `registry` isn't
+/// sparse, this falls back to probing its classic git index directly (see
+/// [`classic_index_has_any_version`]) in a single clone shared across every package, rather than
+/// one clone per package, so the propagation-delay retry this backs still works for git-index
+/// registries without multiplying the clone cost by the number of packages. Returns `Ok(None)`
+/// (as "unknown") only when `registry` couldn't be resolved to an index at all, since we then
+/// have no way to check.
+pub(crate) fn packages_have_any_version(
+    registry: Option<&str>,
+    packages: &[&str],
+) -> anyhow::Result<Option<Vec<bool>>> {
+    if sparse_index_base_url(registry)?.is_some() {
+        return packages
+            .iter()
+            .map(|package| {
+                let records = fetch_index_records(registry, package)?
+                    .context("registry unexpectedly stopped looking sparse mid-lookup")?;
+                Ok(!records.is_empty())
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(Some);
+    }
+
+    match classic_git_index_url(registry)? {
+        Some(repo_url) => classic_index_has_any_version(&repo_url, packages).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Resolves `registry`'s index URL from cargo's configuration, if it's configured to use the
+/// classic git protocol rather than the sparse one (i.e. its `index` isn't `sparse+...`).
+/// Returns `None` for sparse registries (already handled by [`sparse_index_base_url`]), for
+/// crates.io (which no longer supports the classic protocol), and for unconfigured registries.
+fn classic_git_index_url(registry: Option<&str>) -> anyhow::Result<Option<String>> {
+    let Some(name) = registry else {
+        return Ok(None);
+    };
+    let Some(index_url) = registry_index_url_from_cargo_config(name)? else {
+        return Ok(None);
+    };
+    Ok((!index_url.starts_with("sparse+")).then_some(index_url))
+}
+
+/// Whether each of `packages` has any version in a classic git-index registry, checked by
+/// looking up each one's index file via [`fetch_classic_index_paths`], in a single clone shared
+/// across all of them rather than one clone per package.
+fn classic_index_has_any_version(repo_url: &str, packages: &[&str]) -> anyhow::Result<Vec<bool>> {
+    let paths: Vec<String> = packages.iter().map(|package| index_path(package)).collect();
+    let path_refs: Vec<&str> = paths.iter().map(String::as_str).collect();
+    let contents = fetch_classic_index_paths(repo_url, &path_refs)?;
+    Ok(contents.into_iter().map(|content| content.is_some()).collect())
+}
+
+/// Clones just `paths` out of a classic git-index registry's repo at `repo_url`, into a
+/// throwaway directory using `git sparse-checkout` (a partial checkout, like the one
+/// [`checkout_package_sparse`](super::checkout_package_sparse) makes for release tags with
+/// `read-tree`/`checkout-index`), and returns
+/// each path's contents in order, or `None` for a path that doesn't exist in the index (e.g. an
+/// unpublished crate, or a registry with no `config.json`).
+///
+/// `--filter=blob:none` on top of `--depth 1` is what actually keeps this cheap: `sparse-checkout`
+/// alone only limits what's materialized into the working tree, not what's transferred — without
+/// the filter, `git fetch` still sends every blob reachable from that commit, i.e. the registry's
+/// whole current index. With it, blobs outside the sparse-checkout's paths are never fetched at
+/// all.
+///
+/// Authenticating this clone isn't implemented: classic git-index registries that require
+/// credentials just to read the index are rare, and doing so would mean reimplementing git's own
+/// credential helper resolution; such a registry fails here the same way it already failed
+/// before this fallback existed.
+fn fetch_classic_index_paths(
+    repo_url: &str,
+    paths: &[&str],
+) -> anyhow::Result<Vec<Option<String>>> {
+    let dir = tempfile::tempdir().context("failed to create temp dir for registry index probe")?;
+
+    let run_git = |args: &[&str]| -> anyhow::Result<std::process::Output> {
+        std::process::Command::new("git")
+            .current_dir(dir.path())
+            .args(args)
+            .output()
+            .context("failed to spawn git")
+    };
+    let ensure_success = |output: &std::process::Output, what: &str| -> anyhow::Result<()> {
+        anyhow::ensure!(
+            output.status.success(),
+            "{what}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Ok(())
+    };
+
+    ensure_success(
+        &run_git(&["init", "-q"])?,
+        "failed to init temp git repo for registry index probe",
+    )?;
+    ensure_success(
+        &run_git(&["remote", "add", "origin", repo_url])?,
+        "failed to configure registry index remote",
+    )?;
+    let mut sparse_checkout_args = vec!["sparse-checkout", "set", "--no-cone"];
+    sparse_checkout_args.extend(paths.iter().copied());
+    ensure_success(
+        &run_git(&sparse_checkout_args)?,
+        "failed to configure sparse checkout for registry index probe",
+    )?;
+    let fetch = run_git(&[
+        "fetch",
+        "--depth",
+        "1",
+        "--filter=blob:none",
+        "origin",
+        "HEAD",
+    ])?;
+    ensure_success(&fetch, &format!("failed to fetch registry index at {repo_url}"))?;
+    ensure_success(
+        &run_git(&["checkout", "-q", "FETCH_HEAD"])?,
+        "failed to check out registry index probe",
+    )?;
+
+    let mut contents = Vec::with_capacity(paths.len());
+    for path in paths {
+        let file = dir.path().join(path);
+        contents.push(if file.exists() {
+            Some(
+                fs_err::read_to_string(&file)
+                    .with_context(|| format!("failed to read '{path}' from registry index"))?,
+            )
+        } else {
+            None
+        });
+    }
+    Ok(contents)
+}
+
+/// Fetches and parses `package`'s sparse-index file, returning `None` when `registry` isn't
+/// sparse, or `Some(records)` (possibly empty for an unpublished crate) otherwise.
+fn fetch_index_records(
+    registry: Option<&str>,
+    package: &str,
+) -> anyhow::Result<Option<Vec<IndexRecord>>> {
+    let Some(base_url) = sparse_index_base_url(registry)? else {
+        return Ok(None);
+    };
+
+    let url = format!("{base_url}/{}", index_path(package));
+    let client = reqwest::blocking::Client::new();
+    let mut request = client.get(&url);
+    if let Some(token) = registry_token(registry)? {
+        request = request.header(reqwest::header::AUTHORIZATION, token);
+    }
+    let response = request
+        .send()
+        .with_context(|| format!("failed to fetch sparse registry index at {url}"))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(Some(vec![]));
+    }
+    let body = response
+        .error_for_status()
+        .with_context(|| format!("registry index returned an error for {url}"))?
+        .text()
+        .with_context(|| format!("failed to read response body from {url}"))?;
+
+    Ok(Some(parse_index_records(&body)))
+}
+
+/// Parses a sparse- or classic-index file's contents: newline-delimited JSON, one [`IndexRecord`]
+/// per line. Lines that fail to parse are skipped rather than failing the whole file, the same
+/// way cargo itself tolerates fields it doesn't understand yet.
+fn parse_index_records(body: &str) -> Vec<IndexRecord> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str::<IndexRecord>(line).ok())
+        .collect()
+}
+
+/// The path of `package`'s index file within a sparse registry, following cargo's layout:
+/// - 1-char names: `1/<name>`
+/// - 2-char names: `2/<name>`
+/// - 3-char names: `3/<first-char>/<name>`
+/// - everything else: `<first-two>/<next-two>/<name>`
+///
+/// All path segments are lowercased, as required by the sparse index spec.
+pub(crate) fn index_path(package: &str) -> String {
+    let name = package.to_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    }
+}
+
+/// A registry's sparse-index `config.json`, which (among other things) declares the URL
+/// template used to download a crate's tarball.
+#[derive(Deserialize)]
+struct IndexConfig {
+    dl: String,
+}
+
+/// Resolves the tarball download URL for `package`'s `version` on `registry`, following the
+/// `dl` template published in the registry's `config.json` (see the [index format
+/// spec](https://doc.rust-lang.org/cargo/reference/registries.html#index-format)), fetched over
+/// HTTP for a sparse registry or cloned the same way [`fetch_latest_version`] does for a classic
+/// git-index one.
+pub(crate) fn dl_url(registry: Option<&str>, name: &str, version: &Version) -> anyhow::Result<String> {
+    let config = index_config(registry)?;
+    Ok(render_dl_template(&config.dl, name, version))
+}
+
+/// Fetches and parses `registry`'s `config.json`, whether it's a sparse or classic git-index
+/// registry. Errors when `registry` couldn't be resolved to an index at all.
+fn index_config(registry: Option<&str>) -> anyhow::Result<IndexConfig> {
+    if let Some(base_url) = sparse_index_base_url(registry)? {
+        return fetch_index_config(registry, &base_url);
+    }
+
+    let repo_url = classic_git_index_url(registry)?.with_context(|| {
+        format!(
+            "registry '{}' isn't configured (checked cargo's config for a `registries.<name>.index`)",
+            registry.unwrap_or("crates.io")
+        )
+    })?;
+    let contents = fetch_classic_index_paths(&repo_url, &["config.json"])?
+        .into_iter()
+        .next()
+        .flatten()
+        .with_context(|| format!("registry index at {repo_url} has no config.json"))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse registry config at {repo_url}"))
+}
+
+/// Fetches and parses `{base_url}/config.json`, authenticated the same way [`fetch_latest_version`]
+/// authenticates an index lookup.
+fn fetch_index_config(registry: Option<&str>, base_url: &str) -> anyhow::Result<IndexConfig> {
+    let url = format!("{base_url}/config.json");
+    let client = reqwest::blocking::Client::new();
+    let mut request = client.get(&url);
+    if let Some(token) = registry_token(registry)? {
+        request = request.header(reqwest::header::AUTHORIZATION, token);
+    }
+    request
+        .send()
+        .with_context(|| format!("failed to fetch registry config at {url}"))?
+        .error_for_status()
+        .with_context(|| format!("registry returned an error for {url}"))?
+        .json::<IndexConfig>()
+        .with_context(|| format!("failed to parse registry config at {url}"))
+}
+
+/// Expands a registry's `dl` url template for `name`/`version`. If `template` contains no
+/// `{...}` markers, `/{name}/{version}/download` is appended (the shorthand form cargo falls
+/// back to); otherwise, the `{crate}` and `{version}` markers are substituted. `{prefix}`,
+/// `{lowerprefix}` and `{sha256-checksum}` aren't substituted: no registry release-plz has been
+/// used against so far relies on them.
+fn render_dl_template(template: &str, name: &str, version: &Version) -> String {
+    if !template.contains('{') {
+        return format!("{template}/{name}/{version}/download");
+    }
+    template
+        .replace("{crate}", name)
+        .replace("{version}", &version.to_string())
+}
+
+/// Resolves the base URL of `registry`'s sparse index from cargo's configuration, returning
+/// `None` when the registry isn't configured to use the sparse protocol (`sparse+https://…`).
+pub(crate) fn sparse_index_base_url(registry: Option<&str>) -> anyhow::Result<Option<String>> {
+    let index_url = match registry {
+        None => "sparse+https://index.crates.io".to_string(),
+        Some(name) => match registry_index_url_from_cargo_config(name)? {
+            Some(url) => url,
+            None => return Ok(None),
+        },
+    };
+
+    Ok(index_url
+        .strip_prefix("sparse+")
+        .map(|url| url.trim_end_matches('/').to_string()))
+}
+
+/// Reads `registries.<name>.index` from cargo's configuration. Like `cargo` itself, this checks
+/// `.cargo/config.toml` in the current directory and its ancestors (so a registry committed at
+/// the project/workspace root is picked up) before falling back to `$CARGO_HOME/config.toml`.
+fn registry_index_url_from_cargo_config(name: &str) -> anyhow::Result<Option<String>> {
+    for config in read_cargo_configs("config.toml")? {
+        let index = config
+            .get("registries")
+            .and_then(|registries| registries.get(name))
+            .and_then(|registry| registry.get("index"))
+            .and_then(|index| index.as_str());
+        if let Some(index) = index {
+            return Ok(Some(index.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Resolves the token used to authenticate requests to `registry` (or crates.io, if `None`),
+/// checking the same sources `cargo` itself does, in the same order:
+/// 1. `CARGO_REGISTRIES_<NAME>_TOKEN` (or `CARGO_REGISTRY_TOKEN` for crates.io).
+/// 2. `registries.<name>.token` (or `registry.token` for crates.io) in cargo's credentials file.
+///
+/// Credential providers aren't consulted: for a registry that relies solely on one, this returns
+/// `None`, and its index, `config.json` and tarball requests are all sent without a token.
+pub(crate) fn registry_token(registry: Option<&str>) -> anyhow::Result<Option<String>> {
+    let env_var = match registry {
+        Some(name) => format!(
+            "CARGO_REGISTRIES_{}_TOKEN",
+            name.to_uppercase().replace('-', "_")
+        ),
+        None => "CARGO_REGISTRY_TOKEN".to_string(),
+    };
+    if let Ok(token) = std::env::var(&env_var) {
+        return Ok(Some(token));
+    }
+
+    // Unlike the registry `index` url, the token is deliberately not looked up in a
+    // project-local config: credentials aren't meant to be committed, so cargo itself only
+    // ever reads them from `$CARGO_HOME`.
+    let Some(credentials) = read_cargo_home_config("credentials.toml")? else {
+        return Ok(None);
+    };
+
+    let token = match registry {
+        Some(name) => credentials
+            .get("registries")
+            .and_then(|registries| registries.get(name))
+            .and_then(|registry| registry.get("token")),
+        None => credentials.get("registry").and_then(|r| r.get("token")),
+    }
+    .and_then(|token| token.as_str())
+    .map(str::to_string);
+
+    Ok(token)
+}
+
+/// Reads and parses `<dir>/<file_name>` for every `dir` cargo would merge configuration from:
+/// the current directory and each of its ancestors (closest first), then `$CARGO_HOME`. Missing
+/// files are skipped; the legacy file name (without the `.toml` extension) is also tried.
+fn read_cargo_configs(file_name: &str) -> anyhow::Result<Vec<toml::Value>> {
+    let mut configs = vec![];
+
+    if let Ok(cwd) = std::env::current_dir() {
+        let mut dir = Some(cwd.as_path());
+        while let Some(current) = dir {
+            if let Some(config) = read_config_file(&current.join(".cargo"), file_name)? {
+                configs.push(config);
+            }
+            dir = current.parent();
+        }
+    }
+
+    if let Some(config) = read_cargo_home_config(file_name)? {
+        configs.push(config);
+    }
+
+    Ok(configs)
+}
+
+/// Reads and parses `$CARGO_HOME/<file_name>` (or its legacy, extension-less name).
+fn read_cargo_home_config(file_name: &str) -> anyhow::Result<Option<toml::Value>> {
+    let Ok(cargo_home) = home::cargo_home() else {
+        return Ok(None);
+    };
+    read_config_file(&cargo_home, file_name)
+}
+
+/// Reads and parses `<dir>/<file_name>`, or `<dir>/<file_name without ".toml">` if that's the one
+/// that exists (cargo accepted bare `config`/`credentials` before it started preferring the
+/// `.toml`-suffixed names). Returns `None` when neither file exists.
+fn read_config_file(dir: &std::path::Path, file_name: &str) -> anyhow::Result<Option<toml::Value>> {
+    let legacy_file_name = file_name.trim_end_matches(".toml");
+    let config_path = [dir.join(file_name), dir.join(legacy_file_name)]
+        .into_iter()
+        .find(|path| path.exists());
+    let Some(config_path) = config_path else {
+        return Ok(None);
+    };
+
+    let config = fs_err::read_to_string(&config_path)
+        .with_context(|| format!("failed to read cargo config at {}", config_path.display()))?;
+    let config: toml::Value = toml::from_str(&config)
+        .with_context(|| format!("failed to parse cargo config at {}", config_path.display()))?;
+
+    Ok(Some(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_path_follows_cargo_sparse_index_layout() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("ab"), "2/ab");
+        assert_eq!(index_path("abc"), "3/a/abc");
+        assert_eq!(index_path("abcd"), "ab/cd/abcd");
+        assert_eq!(index_path("serde"), "se/rd/serde");
+        // Lowercased, as required by the sparse index spec.
+        assert_eq!(index_path("Abcd"), "ab/cd/abcd");
+    }
+
+    #[test]
+    fn sparse_index_base_url_defaults_to_crates_io() {
+        assert_eq!(
+            sparse_index_base_url(None).unwrap(),
+            Some("https://index.crates.io".to_string())
+        );
+    }
+
+    #[test]
+    fn render_dl_template_substitutes_crate_and_version_markers() {
+        let version = Version::parse("1.2.3").unwrap();
+
+        assert_eq!(
+            render_dl_template("https://example.com/api/v1/crates/{crate}/{version}/download", "foo", &version),
+            "https://example.com/api/v1/crates/foo/1.2.3/download"
+        );
+    }
+
+    #[test]
+    fn render_dl_template_appends_shorthand_path_when_template_has_no_markers() {
+        let version = Version::parse("1.2.3").unwrap();
+
+        assert_eq!(
+            render_dl_template("https://example.com/dl", "foo", &version),
+            "https://example.com/dl/foo/1.2.3/download"
+        );
+    }
+
+    #[test]
+    fn registry_token_falls_back_to_none_without_env_var_or_credentials() {
+        // No registry by this name is configured anywhere in this sandbox, and we don't set the
+        // corresponding env var, so this should resolve to "no token" rather than erroring.
+        assert_eq!(
+            registry_token(Some("release-plz-sparse-index-test-registry-that-does-not-exist")).unwrap(),
+            None
+        );
+    }
+}